@@ -8,14 +8,38 @@
 //! [`Sioctl::controls()`] and callbacks for subsequent changes can be requested
 //! via [`Sioctl::watch()`].
 //!
-//! There is currently way to set the value of controls. If this would be useful
-//! to you, please feel free to submit a PR.
+//! Controls can be written with [`Sioctl::new_rw()`] and
+//! [`Sioctl::set_value()`].
+//!
+//! ## `tokio`
+//!
+//! With the `tokio` feature enabled, [`Sioctl::into_stream()`] consumes the
+//! interface and returns a [`ControlStream`], which implements
+//! [`futures::Stream`] and can be polled from a `tokio` runtime instead of
+//! spawning a [`Watcher`] thread.
 //!
 //! [`sndio`]: http://www.sndio.org/
 //! [`sioctl_open(3)`]: https://man.openbsd.org/sioctl_open.3
 //! [`Sioctl::new()`]: struct.Sioctl.html#method.new
 //! [`Sioctl::controls()`]: struct.Sioctl.html#method.controls
 //! [`Sioctl::watch()`]: struct.Sioctl.html#method.watch
+//! [`Sioctl::new_rw()`]: struct.Sioctl.html#method.new_rw
+//! [`Sioctl::set_value()`]: struct.Sioctl.html#method.set_value
+//! [`Sioctl::into_stream()`]: struct.Sioctl.html#method.into_stream
+//! [`ControlStream`]: struct.ControlStream.html
+//! [`futures::Stream`]: https://docs.rs/futures/latest/futures/stream/trait.Stream.html
+//!
+//! ## `mio`
+//!
+//! With the `mio` feature enabled, [`Sioctl::into_source()`] consumes the
+//! interface and returns a [`SioctlSource`], which implements
+//! [`mio::event::Source`] so that `sndio` control events can be registered
+//! with an existing [`mio::Poll`] alongside a caller's own sockets or pipes.
+//!
+//! [`Sioctl::into_source()`]: struct.Sioctl.html#method.into_source
+//! [`SioctlSource`]: struct.SioctlSource.html
+//! [`mio::event::Source`]: https://docs.rs/mio/latest/mio/event/trait.Source.html
+//! [`mio::Poll`]: https://docs.rs/mio/latest/mio/struct.Poll.html
 //!
 //! ## Example
 //!
@@ -46,20 +70,42 @@
 
 use std::collections::HashMap;
 use std::ffi::CStr;
+use std::fmt;
 use std::mem;
 use std::os::fd::{AsRawFd, OwnedFd};
 use std::os::raw::{c_char, c_int, c_uint, c_void};
 use std::os::unix::io::RawFd;
-use std::sync::{Arc, Mutex};
+use std::sync::{mpsc, Arc, Mutex};
 use std::thread;
 use std::thread::JoinHandle;
+use std::time::Duration;
 
 use libc::{poll, EINTR, POLLIN, SIGHUP};
 use nix::errno::errno;
 use sndio_sys::*;
 
+#[cfg(feature = "tokio")]
+mod stream;
+
+#[cfg(feature = "tokio")]
+pub use crate::stream::ControlStream;
+
+#[cfg(feature = "mio")]
+mod source;
+
+#[cfg(feature = "mio")]
+pub use crate::source::{Disconnected, SioctlSource};
+
+/// Opaque address of a `sndio` control, as used by `sioctl_setval(3)`.
+///
+/// Identifies a [`Control`] (see its [`address()`]); [`Sioctl::set_value()`]
+/// writes to the address of the `Control` it is given.
+///
+/// [`Control`]: struct.Control.html
+/// [`address()`]: struct.Control.html#method.address
+/// [`Sioctl::set_value()`]: struct.Sioctl.html#method.set_value
 #[derive(Clone, Copy, Debug, Hash, Eq, PartialEq)]
-struct Address(c_uint);
+pub struct Address(c_uint);
 
 /// A `sndio` control, with its value.
 ///
@@ -72,8 +118,52 @@ pub struct Control {
     pub name: String,
     pub func: String,
     pub value: u8,
+    /// The largest value this control accepts, as reported by `sndio`.
+    pub max_value: u8,
+    address: Address,
+}
+
+impl Control {
+    /// The opaque address of this control, which [`Sioctl::set_value()`]
+    /// passes to `sioctl_setval(3)`.
+    ///
+    /// [`Sioctl::set_value()`]: struct.Sioctl.html#method.set_value
+    pub fn address(&self) -> Address {
+        self.address
+    }
 }
 
+/// An error returned by [`Sioctl::set_value()`].
+///
+/// [`Sioctl::set_value()`]: struct.Sioctl.html#method.set_value
+#[derive(Clone, Copy, Debug)]
+pub enum SetValueError {
+    /// `value` was greater than the control's `max_value`.
+    OutOfRange { value: u8, max_value: u8 },
+    /// `sioctl_setval(3)` reported failure, e.g. because the interface was
+    /// opened with [`Sioctl::new()`] rather than [`Sioctl::new_rw()`], or
+    /// the control's address was no longer valid.
+    ///
+    /// [`Sioctl::new()`]: struct.Sioctl.html#method.new
+    /// [`Sioctl::new_rw()`]: struct.Sioctl.html#method.new_rw
+    Failed,
+}
+
+impl fmt::Display for SetValueError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SetValueError::OutOfRange { value, max_value } => write!(
+                f,
+                "value {} is out of range for control (max {})",
+                value, max_value
+            ),
+            SetValueError::Failed => write!(f, "sioctl_setval failed"),
+        }
+    }
+}
+
+impl std::error::Error for SetValueError {}
+
 #[derive(Debug)]
 struct Handle(*mut sioctl_hdl);
 
@@ -115,12 +205,28 @@ pub struct Sioctl {
 impl Sioctl {
     /// Opens an interface to the `sndio` controls of the `SIO_DEVANY` device.
     pub fn new() -> Self {
-        let handle = unsafe { sioctl_open(SIO_DEVANY.as_ptr() as *const _, SIOCTL_READ, 0) };
+        Self::open(SIOCTL_READ)
+    }
+
+    /// Opens a writable interface to the `sndio` controls of the
+    /// `SIO_DEVANY` device.
+    ///
+    /// Unlike [`new()`], this allows [`set_value()`] to be called on the
+    /// returned interface.
+    ///
+    /// [`new()`]: #method.new
+    /// [`set_value()`]: #method.set_value
+    pub fn new_rw() -> Self {
+        Self::open(SIOCTL_READ | SIOCTL_WRITE)
+    }
+
+    fn open(mode: c_uint) -> Self {
+        let handle = unsafe { sioctl_open(SIO_DEVANY.as_ptr() as *const _, mode, 0) };
         let handle = Handle(handle);
 
         let inner = Mutex::new(Inner {
             controls: HashMap::new(),
-            callback: None,
+            consumer: None,
         });
         let shared = Arc::new(Shared { inner });
 
@@ -154,6 +260,39 @@ impl Sioctl {
         inner.controls.values().cloned().collect()
     }
 
+    /// Sets the value of a control obtained from [`controls()`].
+    ///
+    /// The interface must have been opened with [`new_rw()`] for this to
+    /// succeed. `value` is validated against the control's
+    /// [`max_value`][Control::max_value] before being sent to `sndio`.
+    ///
+    /// Serialized against the `sioctl_ondesc`/`sioctl_onval` callbacks via
+    /// the same `Mutex` used for [`controls()`], so a write can't race with
+    /// a callback observing stale state.
+    ///
+    /// [`controls()`]: #method.controls
+    /// [`new_rw()`]: #method.new_rw
+    /// [Control::max_value]: struct.Control.html#structfield.max_value
+    pub fn set_value(&self, control: &Control, value: u8) -> Result<(), SetValueError> {
+        if value > control.max_value {
+            return Err(SetValueError::OutOfRange {
+                value,
+                max_value: control.max_value,
+            });
+        }
+
+        // Held for the duration of the call to serialize against the
+        // ondesc/onval callbacks, which run with the same lock held.
+        let _inner = self.shared.inner.lock().unwrap();
+
+        let ok = unsafe { sioctl_setval(self.handle.as_ptr(), control.address.0, value as c_uint) };
+        if ok == 0 {
+            return Err(SetValueError::Failed);
+        }
+
+        Ok(())
+    }
+
     /// Watches for changes to each `sndio` control.
     ///
     /// Accepts a callback which is called with a [`Control`] each time the
@@ -165,12 +304,32 @@ impl Sioctl {
     /// [`Control`]: struct.Control.html
     /// [`Watcher`]: struct.Watcher.html
     pub fn watch<C>(self, callback: C) -> Watcher
+    where
+        C: Fn(&Control) + Send + Sync + 'static,
+    {
+        self.watch_with_poll_interval(callback, None)
+    }
+
+    /// Like [`watch()`], but has the background thread wake up every
+    /// `poll_interval` even if nothing has happened, rather than blocking
+    /// indefinitely in `poll(2)`.
+    ///
+    /// This gives a hung `sndio` server fd a chance to be noticed, instead of
+    /// wedging the watcher thread forever. Pass `None` for the previous
+    /// behaviour of blocking indefinitely.
+    ///
+    /// [`watch()`]: #method.watch
+    pub fn watch_with_poll_interval<C>(
+        self,
+        callback: C,
+        poll_interval: Option<Duration>,
+    ) -> Watcher
     where
         C: Fn(&Control) + Send + Sync + 'static,
     {
         {
             let mut inner = self.shared.inner.lock().unwrap();
-            inner.callback = Some(Box::new(callback));
+            inner.consumer = Some(Consumer::Callback(Box::new(callback)));
         }
 
         // We create a pipe so that we can wake up polling_thread() to tell it
@@ -179,19 +338,89 @@ impl Sioctl {
         let (close_rx, close_tx) = nix::unistd::pipe().unwrap();
 
         let handle = self.handle;
-        let thread_handle = thread::spawn(move || polling_thread(handle, close_rx));
+        let thread_handle = thread::spawn(move || polling_thread(handle, close_rx, poll_interval));
 
         Watcher {
             shared_ptr: self.shared_ptr,
             thread_handle: Some(thread_handle),
             close_tx: close_tx.as_raw_fd(),
+            close_sent: false,
+            done_rx: None,
+        }
+    }
+
+    /// Consumes `self` to obtain the underlying pieces needed by an
+    /// alternative consumer (such as [`ControlStream`]) which drives
+    /// `sioctl_revents` itself instead of spawning a [`Watcher`] thread.
+    ///
+    /// Subsequent changes are pushed into a queue on [`Shared`], rather than
+    /// invoking a user-provided callback.
+    ///
+    /// [`ControlStream`]: stream/struct.ControlStream.html
+    /// [`Watcher`]: struct.Watcher.html
+    #[allow(dead_code)]
+    fn into_parts(self) -> (Handle, Arc<Shared>, SharedPtr) {
+        {
+            let mut inner = self.shared.inner.lock().unwrap();
+            inner.consumer = Some(Consumer::Queue(Default::default()));
         }
+
+        (self.handle, self.shared, self.shared_ptr)
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl Sioctl {
+    /// Consumes `self` and returns a [`ControlStream`], a
+    /// [`futures::Stream`] of [`Control`] changes driven by a `tokio`
+    /// runtime via [`tokio::io::unix::AsyncFd`], rather than a dedicated
+    /// [`Watcher`] thread.
+    ///
+    /// [`ControlStream`]: struct.ControlStream.html
+    /// [`futures::Stream`]: https://docs.rs/futures/latest/futures/stream/trait.Stream.html
+    /// [`Control`]: struct.Control.html
+    /// [`tokio::io::unix::AsyncFd`]: https://docs.rs/tokio/latest/tokio/io/unix/struct.AsyncFd.html
+    /// [`Watcher`]: struct.Watcher.html
+    pub fn into_stream(self) -> ControlStream {
+        let (handle, shared, shared_ptr) = self.into_parts();
+        ControlStream::new(handle, shared, shared_ptr)
+    }
+}
+
+#[cfg(feature = "mio")]
+impl Sioctl {
+    /// Consumes `self` and returns a [`SioctlSource`] which implements
+    /// [`mio::event::Source`], so `sndio` control events can be registered
+    /// with an existing [`mio::Poll`] instead of spawning a dedicated
+    /// [`Watcher`] thread.
+    ///
+    /// [`SioctlSource`]: struct.SioctlSource.html
+    /// [`mio::event::Source`]: https://docs.rs/mio/latest/mio/event/trait.Source.html
+    /// [`mio::Poll`]: https://docs.rs/mio/latest/mio/struct.Poll.html
+    /// [`Watcher`]: struct.Watcher.html
+    pub fn into_source(self) -> SioctlSource {
+        let (handle, shared, shared_ptr) = self.into_parts();
+        SioctlSource::new(handle, shared, shared_ptr)
     }
 }
 
 struct Inner {
     controls: HashMap<Address, Control>,
-    callback: Option<Box<dyn Fn(&Control) + Send + Sync>>,
+    consumer: Option<Consumer>,
+}
+
+/// How changed [`Control`]s are delivered to whoever is driving
+/// `sioctl_revents`.
+///
+/// [`Control`]: struct.Control.html
+enum Consumer {
+    /// Invoke a user-provided callback as soon as a control changes.
+    Callback(Box<dyn Fn(&Control) + Send + Sync>),
+    /// Buffer changed controls for a poll-driven consumer (such as
+    /// [`ControlStream`]) to drain.
+    ///
+    /// [`ControlStream`]: stream/struct.ControlStream.html
+    Queue(std::collections::VecDeque<Control>),
 }
 
 /// Shared between the Rust objects and the C callbacks.
@@ -215,12 +444,38 @@ impl Shared {
 
         // Intentionally call with the lock, so the callback can rely on
         // serial messages.
-        if let Some(control) = inner.controls.get(&address) {
-            if let Some(callback) = &inner.callback {
-                (callback)(control)
+        if let Some(control) = inner.controls.get(&address).cloned() {
+            match &mut inner.consumer {
+                Some(Consumer::Callback(callback)) => (callback)(&control),
+                Some(Consumer::Queue(queue)) => queue.push_back(control),
+                None => {}
             }
         }
     }
+
+    /// Pops the oldest [`Control`] buffered by a [`Consumer::Queue`], if any.
+    ///
+    /// [`Control`]: struct.Control.html
+    #[allow(dead_code)]
+    fn pop_queued(&self) -> Option<Control> {
+        let mut inner = self.inner.lock().unwrap();
+        match &mut inner.consumer {
+            Some(Consumer::Queue(queue)) => queue.pop_front(),
+            _ => None,
+        }
+    }
+
+    /// Drains every [`Control`] buffered by a [`Consumer::Queue`].
+    ///
+    /// [`Control`]: struct.Control.html
+    #[allow(dead_code)]
+    fn drain_queued(&self) -> Vec<Control> {
+        let mut inner = self.inner.lock().unwrap();
+        match &mut inner.consumer {
+            Some(Consumer::Queue(queue)) => queue.drain(..).collect(),
+            _ => Vec::new(),
+        }
+    }
 }
 
 /// Wrapper around Arc<Shared>::into_raw() to ensure it is eventually Dropped.
@@ -251,7 +506,12 @@ impl Drop for SharedPtr {
 pub struct Watcher {
     shared_ptr: SharedPtr,
     close_tx: RawFd,
+    close_sent: bool,
     thread_handle: Option<JoinHandle<()>>,
+    // Set once a prior join_timeout() has handed the join off to a helper
+    // thread, so a later join()/join_timeout() can keep waiting on the same
+    // shutdown instead of starting a new helper.
+    done_rx: Option<mpsc::Receiver<thread::Result<()>>>,
 }
 
 impl Watcher {
@@ -260,12 +520,71 @@ impl Watcher {
     /// This can be called multiple times and will do nothing if the watcher has
     /// already stopped.
     pub fn join(&mut self) {
+        self.signal_close();
+
+        if let Some(done_rx) = mem::replace(&mut self.done_rx, None) {
+            done_rx.recv().unwrap().unwrap();
+            return;
+        }
+
         if let Some(thread_handle) = mem::replace(&mut self.thread_handle, None) {
+            thread_handle.join().unwrap();
+        }
+    }
+
+    /// Like [`join()`], but gives up and returns `false` if the background
+    /// thread hasn't exited within `timeout`, instead of blocking forever.
+    ///
+    /// Returns `true` if the thread exited within `timeout`. If it didn't,
+    /// the `Watcher` is left in a state where [`join()`] or `join_timeout()`
+    /// can be called again to keep waiting for the same shutdown.
+    ///
+    /// [`join()`]: #method.join
+    pub fn join_timeout(&mut self, timeout: Duration) -> bool {
+        self.signal_close();
+
+        // thread::JoinHandle has no join-with-timeout, so hand the join off
+        // to a helper thread and wait on a channel instead. The helper's
+        // receiver is kept around on timeout, so a later call can cheaply
+        // wait on the same helper rather than spawning a new one.
+        let done_rx = match mem::replace(&mut self.done_rx, None) {
+            Some(done_rx) => done_rx,
+            None => {
+                let thread_handle = match mem::replace(&mut self.thread_handle, None) {
+                    Some(thread_handle) => thread_handle,
+                    None => return true,
+                };
+
+                let (done_tx, done_rx) = mpsc::channel();
+                thread::spawn(move || {
+                    let _ = done_tx.send(thread_handle.join());
+                });
+                done_rx
+            }
+        };
+
+        match done_rx.recv_timeout(timeout) {
+            Ok(result) => {
+                result.unwrap();
+                true
+            }
+            Err(mpsc::RecvTimeoutError::Timeout) => {
+                self.done_rx = Some(done_rx);
+                false
+            }
+            Err(mpsc::RecvTimeoutError::Disconnected) => {
+                unreachable!("helper thread always sends before exiting")
+            }
+        }
+    }
+
+    fn signal_close(&mut self) {
+        if !self.close_sent {
             // Close close_tx(), which will cause SIGHUP on close_rx in the
             // thread. The thread will then exit and we can wait for the
             // thread to join.
             nix::unistd::close(self.close_tx).unwrap();
-            thread_handle.join().unwrap();
+            self.close_sent = true;
         }
     }
 }
@@ -276,13 +595,36 @@ impl Drop for Watcher {
     }
 }
 
-fn polling_thread(handle: Handle, close_rx: OwnedFd) {
+/// Builds the `pollfd` array sndio expects for `sioctl_revents()`, by
+/// calling `sioctl_nfds()`/`sioctl_pollfd()` on `handle`.
+///
+/// Shared by `polling_thread` and by alternative consumers (such as
+/// [`ControlStream`] and [`SioctlSource`]) which drive `sioctl_revents()`
+/// themselves.
+///
+/// [`ControlStream`]: stream/struct.ControlStream.html
+/// [`SioctlSource`]: source/struct.SioctlSource.html
+pub(crate) fn build_pollfds(handle: &Handle) -> Vec<pollfd> {
     unsafe {
         let nfds = sioctl_nfds(handle.as_ptr()) as usize;
         let mut pollfds = Vec::with_capacity(nfds);
-        let mut nfds = sioctl_pollfd(handle.as_ptr(), pollfds.as_mut_ptr(), POLLIN as i32) as usize;
+        let nfds = sioctl_pollfd(handle.as_ptr(), pollfds.as_mut_ptr(), POLLIN as i32) as usize;
         pollfds.set_len(nfds);
+        pollfds
+    }
+}
 
+fn polling_thread(handle: Handle, close_rx: OwnedFd, poll_interval: Option<Duration>) {
+    // Clamp rather than let an overly long interval overflow `i32` and wrap
+    // around into a negative (i.e. infinite) timeout.
+    let poll_timeout = poll_interval.map_or(-1, |interval| {
+        interval.as_millis().min(i32::MAX as u128) as i32
+    });
+
+    let mut pollfds = build_pollfds(&handle);
+    let nfds = pollfds.len();
+
+    unsafe {
         // Place the fd that indicates shutdown last, so that it's ignored by
         // sioctl_revents() which will only look at first nfds.
         pollfds.push(pollfd {
@@ -291,10 +633,10 @@ fn polling_thread(handle: Handle, close_rx: OwnedFd) {
             revents: 0,
         });
         let close_nfd = nfds;
-        nfds += 1;
+        let nfds = nfds + 1;
 
         loop {
-            while poll(pollfds.as_mut_ptr(), nfds as u64, -1) < 0 {
+            while poll(pollfds.as_mut_ptr(), nfds as u64, poll_timeout) < 0 {
                 let err = errno();
                 if err != EINTR {
                     panic!("sioctl err: {}", err);
@@ -335,11 +677,14 @@ extern "C" fn ondesc(ptr: *mut c_void, desc: *mut sioctl_desc, value: c_int) {
                 let group = parse_string(&desc.group);
                 let func = parse_string(&desc.func);
                 let value = value as u8;
+                let max_value = desc.maxval as u8;
                 let control = Control {
                     name,
                     group,
                     func,
                     value,
+                    max_value,
+                    address,
                 };
 
                 shared.on_parameter(address, control);
@@ -0,0 +1,139 @@
+//! A [`mio::event::Source`] for `sndio` control events, so they can be
+//! registered with an existing [`mio::Poll`] instead of a dedicated
+//! [`Watcher`] thread.
+//!
+//! [`mio::event::Source`]: https://docs.rs/mio/latest/mio/event/trait.Source.html
+//! [`mio::Poll`]: https://docs.rs/mio/latest/mio/struct.Poll.html
+//! [`Watcher`]: ../struct.Watcher.html
+
+use std::fmt;
+use std::io;
+use std::sync::Arc;
+
+use libc::POLLIN;
+use mio::event::Source;
+use mio::unix::SourceFd;
+use mio::{Interest, Registry, Token};
+use sndio_sys::{pollfd, sioctl_revents};
+
+use crate::{build_pollfds, Control, Handle, Shared, SharedPtr};
+
+/// Returned by [`SioctlSource::process()`] when `sioctl_revents` reports the
+/// device has disconnected (a `SIGHUP` in its revents).
+///
+/// [`SioctlSource::process()`]: struct.SioctlSource.html#method.process
+#[derive(Clone, Copy, Debug)]
+pub struct Disconnected;
+
+impl fmt::Display for Disconnected {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "sndio device disconnected")
+    }
+}
+
+impl std::error::Error for Disconnected {}
+
+/// A `sndio` control interface which implements [`mio::event::Source`], for
+/// interleaving `sndio` control events with a caller's own sockets/pipes in
+/// a single [`mio::Poll`].
+///
+/// Obtained by calling [`Sioctl::into_source()`]. Because `sndio` may expose
+/// more than one fd, all of them are registered under the same [`Token`]
+/// when this is registered with a [`Registry`]. After [`Poll::poll()`]
+/// reports readiness on that token, call [`process()`] to drive
+/// `sioctl_revents` and collect the [`Control`]s that changed.
+///
+/// [`mio::event::Source`]: https://docs.rs/mio/latest/mio/event/trait.Source.html
+/// [`mio::Poll`]: https://docs.rs/mio/latest/mio/struct.Poll.html
+/// [`Sioctl::into_source()`]: struct.Sioctl.html#method.into_source
+/// [`Token`]: https://docs.rs/mio/latest/mio/struct.Token.html
+/// [`Registry`]: https://docs.rs/mio/latest/mio/struct.Registry.html
+/// [`Poll::poll()`]: https://docs.rs/mio/latest/mio/struct.Poll.html#method.poll
+/// [`process()`]: #method.process
+/// [`Control`]: struct.Control.html
+pub struct SioctlSource {
+    handle: Handle,
+    shared: Arc<Shared>,
+    // Kept alive so the `Arc<Shared>` passed to sndio's callbacks stays
+    // valid for as long as we're driving `sioctl_revents`.
+    #[allow(dead_code)]
+    shared_ptr: SharedPtr,
+    pollfds: Vec<pollfd>,
+}
+
+impl SioctlSource {
+    pub(crate) fn new(handle: Handle, shared: Arc<Shared>, shared_ptr: SharedPtr) -> Self {
+        let pollfds = build_pollfds(&handle);
+
+        Self {
+            handle,
+            shared,
+            shared_ptr,
+            pollfds,
+        }
+    }
+
+    /// Drives `sioctl_revents` and returns the [`Control`]s that changed.
+    ///
+    /// Call this after [`Poll::poll()`] reports readiness on the [`Token`]
+    /// this was registered with. Returns `Err(Disconnected)` if `sndio`
+    /// reports the device has disconnected; the source should then be
+    /// deregistered and dropped.
+    ///
+    /// [`Control`]: struct.Control.html
+    /// [`Poll::poll()`]: https://docs.rs/mio/latest/mio/struct.Poll.html#method.poll
+    /// [`Token`]: https://docs.rs/mio/latest/mio/struct.Token.html
+    pub fn process(&mut self) -> Result<Vec<Control>, Disconnected> {
+        // sioctl_revents() relies on `.revents` reflecting genuine kernel
+        // readiness (the same contract `poll(2)` fulfils in
+        // `polling_thread`). All of our fds are registered under a single
+        // `Token`, so `Poll::poll()` can't tell us which one is actually
+        // readable — mark them all, and let sndio's own nonblocking reads
+        // sort out which (if any) had data.
+        for pollfd in &mut self.pollfds {
+            pollfd.revents = POLLIN;
+        }
+
+        let revents = unsafe { sioctl_revents(self.handle.as_ptr(), self.pollfds.as_mut_ptr()) };
+        let controls = self.shared.drain_queued();
+
+        if revents & libc::SIGHUP > 0 {
+            return Err(Disconnected);
+        }
+
+        Ok(controls)
+    }
+}
+
+impl Source for SioctlSource {
+    fn register(
+        &mut self,
+        registry: &Registry,
+        token: Token,
+        interests: Interest,
+    ) -> io::Result<()> {
+        for pollfd in &self.pollfds {
+            SourceFd(&pollfd.fd).register(registry, token, interests)?;
+        }
+        Ok(())
+    }
+
+    fn reregister(
+        &mut self,
+        registry: &Registry,
+        token: Token,
+        interests: Interest,
+    ) -> io::Result<()> {
+        for pollfd in &self.pollfds {
+            SourceFd(&pollfd.fd).reregister(registry, token, interests)?;
+        }
+        Ok(())
+    }
+
+    fn deregister(&mut self, registry: &Registry) -> io::Result<()> {
+        for pollfd in &self.pollfds {
+            SourceFd(&pollfd.fd).deregister(registry)?;
+        }
+        Ok(())
+    }
+}
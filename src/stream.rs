@@ -0,0 +1,124 @@
+//! A [`futures::Stream`] of [`Control`] changes, built on `tokio`'s
+//! [`AsyncFd`].
+//!
+//! [`futures::Stream`]: https://docs.rs/futures/latest/futures/stream/trait.Stream.html
+//! [`Control`]: ../struct.Control.html
+//! [`AsyncFd`]: https://docs.rs/tokio/latest/tokio/io/unix/struct.AsyncFd.html
+
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use futures::Stream;
+use libc::{POLLIN, SIGHUP};
+use sndio_sys::{pollfd, sioctl_revents};
+use tokio::io::unix::AsyncFd;
+
+use crate::{build_pollfds, Control, Handle, Shared, SharedPtr};
+
+/// A handle to a `sndio` fd which [`AsyncFd`] can poll, but which is owned
+/// (and closed) by the `sioctl_hdl` the fd was obtained from, not by us.
+///
+/// [`AsyncFd`]: https://docs.rs/tokio/latest/tokio/io/unix/struct.AsyncFd.html
+struct BorrowedFd(RawFd);
+
+impl AsRawFd for BorrowedFd {
+    fn as_raw_fd(&self) -> RawFd {
+        self.0
+    }
+}
+
+/// A [`Stream`] of [`Control`] changes, obtained by calling
+/// [`Sioctl::into_stream()`].
+///
+/// Each item is yielded as soon as the underlying `sndio` control changes.
+/// The stream ends (yields `None`) if the device is disconnected.
+///
+/// [`Stream`]: https://docs.rs/futures/latest/futures/stream/trait.Stream.html
+/// [`Control`]: struct.Control.html
+/// [`Sioctl::into_stream()`]: struct.Sioctl.html#method.into_stream
+pub struct ControlStream {
+    handle: Handle,
+    shared: Arc<Shared>,
+    // Kept alive so the `Arc<Shared>` passed to sndio's callbacks stays
+    // valid for as long as we're driving `sioctl_revents`.
+    #[allow(dead_code)]
+    shared_ptr: SharedPtr,
+    async_fds: Vec<AsyncFd<BorrowedFd>>,
+    pollfds: Vec<pollfd>,
+}
+
+impl ControlStream {
+    pub(crate) fn new(handle: Handle, shared: Arc<Shared>, shared_ptr: SharedPtr) -> Self {
+        let pollfds = build_pollfds(&handle);
+
+        let async_fds = pollfds
+            .iter()
+            .map(|pollfd| AsyncFd::new(BorrowedFd(pollfd.fd)).unwrap())
+            .collect();
+
+        Self {
+            handle,
+            shared,
+            shared_ptr,
+            async_fds,
+            pollfds,
+        }
+    }
+}
+
+impl Stream for ControlStream {
+    type Item = Control;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Control>> {
+        let this = self.get_mut();
+
+        // Controls that arrived on a previous call to sioctl_revents() but
+        // weren't drained yet (sioctl_revents() can report more than one
+        // changed control per call).
+        if let Some(control) = this.shared.pop_queued() {
+            return Poll::Ready(Some(control));
+        }
+
+        loop {
+            let mut any_ready = false;
+
+            for (ready_idx, async_fd) in this.async_fds.iter_mut().enumerate() {
+                let mut guard = match async_fd.poll_read_ready(cx) {
+                    Poll::Ready(Ok(guard)) => guard,
+                    Poll::Ready(Err(_)) => return Poll::Ready(None),
+                    Poll::Pending => continue,
+                };
+                any_ready = true;
+
+                // Only the fd at `ready_idx` is actually known to be
+                // readable; mirror `poll(2)`'s contract for the rest so
+                // sioctl_revents() doesn't act on stale/zeroed revents.
+                for (i, pollfd) in this.pollfds.iter_mut().enumerate() {
+                    pollfd.revents = if i == ready_idx { POLLIN } else { 0 };
+                }
+
+                let revents =
+                    unsafe { sioctl_revents(this.handle.as_ptr(), this.pollfds.as_mut_ptr()) };
+
+                if revents & SIGHUP > 0 {
+                    return Poll::Ready(None);
+                }
+
+                match this.shared.pop_queued() {
+                    // Don't clear readiness: sioctl_revents() may not have
+                    // drained everything in one call, and clearing here
+                    // risks losing the next edge-triggered wakeup while
+                    // data is still queued behind this control.
+                    Some(control) => return Poll::Ready(Some(control)),
+                    None => guard.clear_ready(),
+                }
+            }
+
+            if !any_ready {
+                return Poll::Pending;
+            }
+        }
+    }
+}